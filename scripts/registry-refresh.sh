@@ -16,14 +16,36 @@
 #   WORK_ROOT          default: /var/lib/theme-browser-refresh
 #   SKIP_PUSH          default: false
 #   SKIP_SUBMODULE_UPDATE default: false
+#   POST_PUSH_HOOK_URL default: unset
+#   POST_PUSH_HOOK_METHOD default: POST
+#   POST_PUSH_HOOK_HEADER default: unset
+#   PUSH_ATTEMPTS      default: 3
+#   PUSH_STRATEGY      default: direct (direct or pull_request)
+#   GIT_SIGNING_KEY_FILE default: unset (use ambient git signing config)
+#   GIT_SIGNING_FORMAT default: ssh (ssh or openpgp)
 #
 # Usage:
 #   theme-browser-registry-refresh [--dry-run]
+#
+# Exit codes:
+#   0  refresh complete
+#   1  refresh failed
+#   3  refresh committed but push failed; workspace kept with local commits
+#   4  refresh pushed but a pull request could not be opened or updated
 
 set -euo pipefail
 
+PUSH_FAILED_EXIT=3
+PULL_REQUEST_FAILED_EXIT=4
+
 dry_run=false
+keep_workspace=false
+published=false
+push_failed=false
+pull_request_failed=false
 RUN_DIR=""
+GNUPG_DIR=""
+SIGNING_KEY=""
 
 build_authenticated_url() {
 	local url="$1"
@@ -44,8 +66,13 @@ build_authenticated_url() {
 	echo "$url"
 }
 
+strip_url_credentials() {
+	local url="$1"
+	echo "${url/#https:\/\/*@github.com\//https://github.com/}"
+}
+
 usage() {
-	sed -n 's/^# //p' "$0" | head -n 19
+	sed -n 's/^# //p' "$0" | head -n 29
 	exit 0
 }
 
@@ -95,6 +122,15 @@ require_env() {
 	fi
 }
 
+require_positive_int() {
+	local key="$1"
+	local value="$2"
+	if [[ ! "$value" =~ ^[1-9][0-9]*$ ]]; then
+		log_error "invalid $key: $value (expected a positive integer)"
+		exit 1
+	fi
+}
+
 run() {
 	if $dry_run; then
 		log_dry "$*"
@@ -137,6 +173,10 @@ show_config() {
 	local plugin_branch="$4"
 	local work_root="$5"
 	local pnpm_store_dir="$6"
+	local push_attempts="$7"
+	local push_strategy="$8"
+	local signing_key_file="$9"
+	local signing_format="${10}"
 
 	log "Run configuration"
 	printf '  repo: %s\n' "$monorepo_url"
@@ -145,10 +185,27 @@ show_config() {
 	printf '  pnpm_store: %s\n' "$pnpm_store_dir"
 	printf '  skip_push: %s\n' "${SKIP_PUSH:-false}"
 	printf '  skip_submodule_update: %s\n' "${SKIP_SUBMODULE_UPDATE:-false}"
+	printf '  push_attempts: %s\n' "$push_attempts"
+	printf '  push_strategy: %s\n' "$push_strategy"
+	if [[ -n "$signing_key_file" ]]; then
+		printf '  signing: %s (%s)\n' "$signing_format" "$signing_key_file"
+	else
+		printf '  signing: ambient git config\n'
+	fi
 	printf '  dry_run: %s\n' "$dry_run"
 }
 
 cleanup() {
+	if [[ -n "$GNUPG_DIR" && -d "$GNUPG_DIR" ]]; then
+		gpgconf --kill gpg-agent >/dev/null 2>&1 || true
+		rm -rf "$GNUPG_DIR"
+	fi
+
+	if $keep_workspace; then
+		log_warn "Keeping workspace with unpushed commits: $RUN_DIR"
+		return 0
+	fi
+
 	if [[ -n "$RUN_DIR" && -d "$RUN_DIR" ]]; then
 		log "Cleaning up workspace: $RUN_DIR"
 		rm -rf "$RUN_DIR"
@@ -175,6 +232,70 @@ configure_repo_identity() {
 	run git -C "$path" config user.email "$GIT_AUTHOR_EMAIL"
 }
 
+load_signing_key() {
+	local key_file="$1"
+	local format="$2"
+
+	if [[ -z "$key_file" ]]; then
+		if [[ -n "${GIT_SIGNING_FORMAT:-}" ]]; then
+			log_warn "GIT_SIGNING_FORMAT is ignored without GIT_SIGNING_KEY_FILE"
+		fi
+		return 0
+	fi
+
+	key_file="$(realpath -m "$key_file")"
+	if [[ ! -f "$key_file" ]]; then
+		log_error "missing signing key file: $key_file"
+		exit 1
+	fi
+
+	case "$format" in
+	ssh)
+		SIGNING_KEY="$key_file"
+		;;
+	openpgp)
+		GNUPG_DIR="$(mktemp -d "${RUN_DIR:-${TMPDIR:-/tmp}}/gnupg.XXXXXX")"
+		export GNUPGHOME="$GNUPG_DIR"
+		gpg --batch --quiet --import "$key_file"
+		SIGNING_KEY="$(gpg --batch --with-colons --import-options show-only --import "$key_file" |
+			awk -F: '/^fpr:/ { print $10; exit }')"
+		if [[ -z "$SIGNING_KEY" ]]; then
+			log_error "no OpenPGP key found in: $key_file"
+			exit 1
+		fi
+		;;
+	*)
+		log_error "unsupported GIT_SIGNING_FORMAT: $format (expected ssh or openpgp)"
+		exit 1
+		;;
+	esac
+}
+
+configure_repo_signing() {
+	local path="$1"
+	local format="$2"
+
+	if [[ -z "$SIGNING_KEY" ]]; then
+		return 0
+	fi
+
+	run git -C "$path" config gpg.format "$format"
+	run git -C "$path" config user.signingkey "$SIGNING_KEY"
+	run git -C "$path" config commit.gpgsign true
+}
+
+clear_repo_signing() {
+	local path="$1"
+
+	if [[ -z "$SIGNING_KEY" ]]; then
+		return 0
+	fi
+
+	git -C "$path" config --unset commit.gpgsign || true
+	git -C "$path" config --unset user.signingkey || true
+	git -C "$path" config --unset gpg.format || true
+}
+
 configure_repo_remote() {
 	local path="$1"
 	local auth_user="$2"
@@ -188,10 +309,20 @@ configure_repo_remote() {
 	fi
 }
 
+clear_repo_credentials() {
+	local path="$1"
+	local remote_url
+
+	remote_url="$(git -C "$path" remote get-url origin)"
+	git -C "$path" remote set-url origin "$(strip_url_credentials "$remote_url")"
+}
+
 configure_repo_access() {
 	local path="$1"
 	local auth_user="$2"
+	local signing_format="$3"
 	configure_repo_identity "$path"
+	configure_repo_signing "$path" "$signing_format"
 	configure_repo_remote "$path" "$auth_user"
 }
 
@@ -210,17 +341,178 @@ commit_if_changed() {
 	return 0
 }
 
+push_with_retry() {
+	local path="$1"
+	local branch="$2"
+	local target="$3"
+	local attempts="$4"
+	local repo_dir="$5"
+	local attempt=1
+	local force=()
+
+	if [[ "$target" != "$branch" ]]; then
+		force=(--force)
+	fi
+
+	while true; do
+		log "Push $path -> origin/$target (attempt $attempt/$attempts)"
+		if git -C "$path" push "${force[@]}" origin "HEAD:refs/heads/$target"; then
+			return 0
+		fi
+
+		if ((attempt >= attempts)); then
+			log_warn "push failed after $attempts attempts: $path -> origin/$target"
+			return 1
+		fi
+
+		log_warn "push failed: $path -> origin/$target"
+		sleep "$((attempt * 5))"
+		attempt=$((attempt + 1))
+
+		if [[ "$target" != "$branch" ]] ||
+			! git -C "$path" fetch origin "$branch" ||
+			git -C "$path" merge-base --is-ancestor "origin/$branch" HEAD; then
+			continue
+		fi
+
+		log_warn "origin/$branch moved; rebasing $path"
+		if ! git -C "$path" rebase "origin/$branch"; then
+			git -C "$path" rebase --abort >/dev/null 2>&1 || true
+			log_warn "rebase onto origin/$branch conflicted: $path"
+			return 1
+		fi
+
+		log "Re-run verification after rebase"
+		if ! run_in_dir "$repo_dir" make verify; then
+			log_warn "verification failed after rebase: $path"
+			return 1
+		fi
+	done
+}
+
+github_api() {
+	local method="$1"
+	local endpoint="$2"
+	shift 2
+
+	curl -fsSL -X "$method" \
+		-H "Authorization: Bearer $GITHUB_TOKEN" \
+		-H "Accept: application/vnd.github+json" \
+		"$@" \
+		"https://api.github.com$endpoint"
+}
+
+github_repo_slug() {
+	local path="$1"
+	local url
+
+	url="$(strip_url_credentials "$(git -C "$path" remote get-url origin)")"
+	if [[ "$url" != https://github.com/* ]]; then
+		log_warn "pull requests need a GitHub remote: $url"
+		return 1
+	fi
+
+	url="${url#https://github.com/}"
+	echo "${url%.git}"
+}
+
+pull_request_body() {
+	local path="$1"
+	local base="$2"
+
+	printf 'Automated refresh of `%s` from `scripts/registry-refresh.sh`.\n\n```text\n%s\n```\n' \
+		"$base" "$(git -C "$path" diff --stat "origin/$base" HEAD)"
+}
+
+open_pull_request() {
+	local path="$1"
+	local base="$2"
+	local head="$3"
+	local title="$4"
+	local slug
+	local body
+	local number
+
+	slug="$(github_repo_slug "$path")" || return 1
+	body="$(pull_request_body "$path" "$base")"
+	number="$(github_api GET "/repos/$slug/pulls?state=open&base=$base&head=${slug%%/*}:$head" |
+		jq -r '.[0].number // empty')" || return 1
+
+	if [[ -n "$number" ]]; then
+		github_api PATCH "/repos/$slug/pulls/$number" \
+			--data "$(jq -n --arg body "$body" '{body: $body}')" >/dev/null || return 1
+		log_ok "Updated pull request $slug#$number"
+		return 0
+	fi
+
+	number="$(github_api POST "/repos/$slug/pulls" \
+		--data "$(jq -n --arg title "$title" --arg head "$head" --arg base "$base" --arg body "$body" \
+			'{title: $title, head: $head, base: $base, body: $body}')" |
+		jq -r '.number')" || return 1
+	log_ok "Opened pull request $slug#$number"
+}
+
+# Returns 1 when the push failed and 2 when only the pull request call failed.
 push_branch() {
 	local path="$1"
 	local branch="$2"
+	local title="$3"
+	local attempts="$4"
+	local repo_dir="$5"
+	local strategy="$6"
+	local head="theme-browser-refresh/$branch"
 
 	if [[ "${SKIP_PUSH:-false}" == "true" ]]; then
 		log_dry "git -C $path push origin $branch"
 		return 0
 	fi
 
-	log "Push $path -> origin/$branch"
-	run git -C "$path" push origin "$branch"
+	if [[ "$strategy" == "direct" ]]; then
+		push_with_retry "$path" "$branch" "$branch" "$attempts" "$repo_dir"
+		return
+	fi
+
+	push_with_retry "$path" "$branch" "$head" "$attempts" "$repo_dir" || return 1
+	if ! open_pull_request "$path" "$branch" "$head" "$title"; then
+		log_warn "pushed $path -> origin/$head but the pull request call failed"
+		return 2
+	fi
+}
+
+publish_repo() {
+	local status=0
+
+	push_branch "$@" || status=$?
+	case "$status" in
+	0) published=true ;;
+	2) pull_request_failed=true ;;
+	*) push_failed=true ;;
+	esac
+}
+
+run_post_push_hook() {
+	local url="$1"
+	local method="$2"
+	local header="$3"
+	local args=(-fsS -o /dev/null -X "$method")
+
+	if [[ -z "$url" ]]; then
+		return 0
+	fi
+
+	if [[ -n "$header" ]]; then
+		args+=(-H "$header")
+	fi
+
+	if [[ "${SKIP_PUSH:-false}" == "true" ]]; then
+		log_dry "curl -X $method ${url%%\?*}"
+		return 0
+	fi
+
+	log "Run post-push hook: $method ${url%%\?*}"
+	if ! curl "${args[@]}" "$url"; then
+		log_warn "post-push hook failed: $method ${url%%\?*}"
+	fi
 }
 
 main() {
@@ -237,7 +529,29 @@ main() {
 	local plugin_branch="${PLUGIN_BRANCH:-main}"
 	local work_root="${WORK_ROOT:-/var/lib/theme-browser-refresh}"
 	local pnpm_store_dir="${PNPM_STORE_DIR:-$work_root/pnpm-store}"
+	local hook_url="${POST_PUSH_HOOK_URL:-}"
+	local hook_method="${POST_PUSH_HOOK_METHOD:-POST}"
+	local hook_header="${POST_PUSH_HOOK_HEADER:-}"
+	local push_attempts="${PUSH_ATTEMPTS:-3}"
+	local push_strategy="${PUSH_STRATEGY:-direct}"
+	local signing_key_file="${GIT_SIGNING_KEY_FILE:-}"
+	local signing_format="${GIT_SIGNING_FORMAT:-ssh}"
+	local registry_message="chore: refresh registry data"
+	local plugin_message="chore: refresh bundled registry"
+	local root_message="chore: refresh submodule pointers"
+	local root_committed=false
 	local repo_dir
+	local path
+
+	require_positive_int PUSH_ATTEMPTS "$push_attempts"
+
+	case "$push_strategy" in
+	direct | pull_request) ;;
+	*)
+		log_error "unsupported PUSH_STRATEGY: $push_strategy (expected direct or pull_request)"
+		exit 1
+		;;
+	esac
 
 	trap 'on_error "$?" "$LINENO" "$BASH_COMMAND"' ERR
 	trap cleanup EXIT
@@ -250,16 +564,19 @@ main() {
 		repo_dir="$RUN_DIR/repo"
 	fi
 
+	load_signing_key "$signing_key_file" "$signing_format"
+
 	show_versions
-	show_config "$monorepo_url" "$monorepo_branch" "$registry_branch" "$plugin_branch" "$work_root" "$pnpm_store_dir"
+	show_config "$monorepo_url" "$monorepo_branch" "$registry_branch" "$plugin_branch" "$work_root" "$pnpm_store_dir" \
+		"$push_attempts" "$push_strategy" "$signing_key_file" "$signing_format"
 
 	log "Clone monorepo"
 	run git clone --branch "$monorepo_branch" --recurse-submodules "$monorepo_url" "$repo_dir"
 
 	if ! $dry_run; then
-		configure_repo_access "$repo_dir" "$auth_user"
-		configure_repo_access "$repo_dir/packages/registry" "$auth_user"
-		configure_repo_access "$repo_dir/packages/plugin" "$auth_user"
+		configure_repo_access "$repo_dir" "$auth_user" "$signing_format"
+		configure_repo_access "$repo_dir/packages/registry" "$auth_user" "$signing_format"
+		configure_repo_access "$repo_dir/packages/plugin" "$auth_user" "$signing_format"
 	fi
 
 	log "Prepare branches"
@@ -272,6 +589,12 @@ main() {
 		run_in_dir "$repo_dir" bash ./scripts/update-submodules.sh
 	fi
 
+	# Pull requests hold the refresh commits, so only merged pointers go to the root.
+	if [[ "$push_strategy" == "pull_request" ]] && ! $dry_run &&
+		commit_if_changed "$repo_dir" "$root_message"; then
+		root_committed=true
+	fi
+
 	log "Install dependencies"
 	run_in_dir "$repo_dir" pnpm install --frozen-lockfile --store-dir "$pnpm_store_dir"
 
@@ -289,24 +612,54 @@ main() {
 	local registry_changed=false
 	local plugin_changed=false
 
-	if commit_if_changed "$repo_dir/packages/registry" "chore: refresh registry data"; then
+	if commit_if_changed "$repo_dir/packages/registry" "$registry_message"; then
 		registry_changed=true
-		push_branch "$repo_dir/packages/registry" "$registry_branch"
+		publish_repo "$repo_dir/packages/registry" "$registry_branch" "$registry_message" \
+			"$push_attempts" "$repo_dir" "$push_strategy"
 	fi
 
-	if commit_if_changed "$repo_dir/packages/plugin" "chore: refresh bundled registry"; then
+	if commit_if_changed "$repo_dir/packages/plugin" "$plugin_message"; then
 		plugin_changed=true
-		push_branch "$repo_dir/packages/plugin" "$plugin_branch"
+		publish_repo "$repo_dir/packages/plugin" "$plugin_branch" "$plugin_message" \
+			"$push_attempts" "$repo_dir" "$push_strategy"
 	fi
 
-	if $registry_changed || $plugin_changed; then
-		run git -C "$repo_dir" add packages/registry packages/plugin
+	if [[ "$push_strategy" == "direct" ]]; then
+		if $registry_changed || $plugin_changed; then
+			run git -C "$repo_dir" add packages/registry packages/plugin
+		fi
+
+		if commit_if_changed "$repo_dir" "$root_message"; then
+			root_committed=true
+		fi
 	fi
 
-	if commit_if_changed "$repo_dir" "chore: refresh submodule pointers"; then
-		push_branch "$repo_dir" "$monorepo_branch"
-	else
+	if ! $root_committed; then
 		log "No root changes to commit"
+	elif [[ "$push_strategy" == "direct" ]] && $push_failed; then
+		log_warn "Skipping root push: submodule commits were not pushed"
+	else
+		publish_repo "$repo_dir" "$monorepo_branch" "$root_message" \
+			"$push_attempts" "$repo_dir" "$push_strategy"
+	fi
+
+	if $push_failed; then
+		for path in "$repo_dir" "$repo_dir/packages/registry" "$repo_dir/packages/plugin"; do
+			clear_repo_credentials "$path"
+			clear_repo_signing "$path"
+		done
+		keep_workspace=true
+		log_error "refresh committed but push failed; local commits kept in $repo_dir"
+		exit "$PUSH_FAILED_EXIT"
+	fi
+
+	if $pull_request_failed; then
+		log_error "refresh pushed but pull requests were not opened; check the token's pull request access"
+		exit "$PULL_REQUEST_FAILED_EXIT"
+	fi
+
+	if $published && [[ "$push_strategy" == "direct" ]]; then
+		run_post_push_hook "$hook_url" "$hook_method" "$hook_header"
 	fi
 
 	log_ok "Refresh run complete"